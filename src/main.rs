@@ -1,11 +1,19 @@
 use clap::Arg;
 use colored::*;
+use futures::stream::{self, StreamExt};
+use std::path::Path;
 use std::process;
 
 mod user;
 use user::*;
 
-fn main() {
+// Default for `--concurrency`: bounds how many files are hashed at once.
+// Hashing is CPU/IO bound, so this just keeps a runaway folder from
+// spawning one blocking thread per file.
+const DEFAULT_HASH_CONCURRENCY: usize = 8;
+
+#[tokio::main]
+async fn main() {
     let matches = clap::App::new("Subpls")
         .version("1.1")
         .about("Download subtitles from OpenSubtitles")
@@ -31,10 +39,77 @@ fn main() {
                 .short("l")
                 .long("language")
                 .value_name("LANGUAGE")
-                .help("SubLanguageID, 'eng' e.g.")
+                .help("SubLanguageID(s), comma-separated, 'eng,spa' e.g.")
                 .default_value("eng")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("retries")
+                .short("r")
+                .long("retries")
+                .value_name("N")
+                .help("How many times to attempt a server call before giving up")
+                .default_value("5")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("concurrency")
+                .long("concurrency")
+                .value_name("N")
+                .help("How many files to hash at once")
+                .default_value("8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("query")
+                .long("query")
+                .value_name("TEXT")
+                .help("Forces a title search instead of one guessed from the filename")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("imdb")
+                .long("imdb")
+                .value_name("ID")
+                .help("Narrows the title search (--query) to a specific IMDB id")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("utf8")
+                .long("utf8")
+                .help("Transcodes saved subtitles to UTF-8"),
+        )
+        .arg(
+            Arg::with_name("to-srt")
+                .long("to-srt")
+                .help("Saves subtitles under a .srt extension"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Reads credentials and defaults from a config file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache")
+                .long("cache")
+                .value_name("FILE")
+                .help("Path to the on-disk login/search cache (not used with --config)")
+                .default_value("subpls-cache.json")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-cache")
+                .long("no-cache")
+                .help("Disables the on-disk login/search cache; with --config, a cache_path from the config file is still read but not saved"),
+        )
+        .arg(
+            Arg::with_name("refresh")
+                .long("refresh")
+                .help("Ignores the cached token and search results, re-fetching both; has no effect with --config"),
+        )
         .arg(
             Arg::with_name("FILE")
                 .required(true)
@@ -61,17 +136,42 @@ fn main() {
         }
     }
 
-    let user = User::login(
-        &username,
-        &password,
-        matches.value_of("language").unwrap_or("en"),
-    );
+    let retries: u32 = matches
+        .value_of("retries")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(User::DEFAULT_RETRIES);
+
+    let cache_path = matches.value_of("cache").map(Path::new);
+
+    let user = match matches.value_of("config") {
+        Some(path) => User::from_config(Path::new(path)).await,
+        None if matches.is_present("no-cache") => {
+            User::login_with_retries(
+                &username,
+                &password,
+                matches.value_of("language").unwrap_or("en"),
+                retries,
+            )
+            .await
+        }
+        None => {
+            User::login_with_cache(
+                &username,
+                &password,
+                matches.value_of("language").unwrap_or("en"),
+                retries,
+                cache_path.unwrap(),
+                matches.is_present("refresh"),
+            )
+            .await
+        }
+    };
 
     if let Err(s) = user {
         eprintln!("{} ({:?})", "could not login to OpenSubtitles".red(), s);
         process::exit(1);
     }
-    let user = user.unwrap();
+    let mut user = user.unwrap();
 
     let msg = "logged in successfully".green();
     println!(
@@ -84,20 +184,49 @@ fn main() {
         }
     );
 
-    let mut movies = Movie::collection(&files);
+    let concurrency: usize = matches
+        .value_of("concurrency")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_HASH_CONCURRENCY);
 
-    for movie in &mut movies {
-        if let Err(e) = movie.compute_os_hash() {
-            eprintln!(
-                "{} {} ({:?})",
-                "could not compute hash for: ".red(),
-                movie.path_str(),
-                e
-            );
+    let mut movies = Movie::collection(&files);
+    if let Some(query) = matches.value_of("query") {
+        for movie in &mut movies {
+            movie.query = Some(query.to_string());
         }
     }
+    if let Some(imdb) = matches.value_of("imdb") {
+        for movie in &mut movies {
+            movie.imdb_id = Some(imdb.to_string());
+        }
+    }
+
+    let movies: Vec<Movie> = stream::iter(movies)
+        .map(|mut movie| async move {
+            match tokio::task::spawn_blocking(move || {
+                let result = movie.compute_os_hash();
+                (movie, result)
+            })
+            .await
+            {
+                Ok((movie, Ok(()))) => movie,
+                Ok((movie, Err(e))) => {
+                    eprintln!(
+                        "{} {} ({:?})",
+                        "could not compute hash for: ".red(),
+                        movie.path_str(),
+                        e
+                    );
+                    movie
+                }
+                Err(e) => panic!("hashing task panicked: {:?}", e),
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
 
-    let search_result = user.search(movies);
+    let search_result = user.search(movies).await;
     if let Err(e) = search_result {
         eprintln!("{} ({:?})", "could not search for subtitles ".red(), e);
         std::process::exit(1);
@@ -107,7 +236,7 @@ fn main() {
     let mut found = 0;
 
     for movie in &mut movies {
-        if movie.os_info.is_none() {
+        if movie.subs.is_empty() {
             continue;
         }
         println!(
@@ -116,7 +245,7 @@ fn main() {
             movie.path_str()
         );
         found += movie.subs.len();
-        movie.filter_subs();
+        movie.filter_subs_with(&user.default_filter);
         if let Some(rating) = movie.present_rating() {
             println!(
                 "  choosing ones with rating: {}/10{}",
@@ -131,7 +260,7 @@ fn main() {
         std::process::exit(1);
     }
 
-    let download_result = user.download(movies);
+    let download_result = user.download(movies).await;
     if let Err(e) = download_result {
         eprintln!("{} ({:?})", "could not download subtitles ".red(), e);
         std::process::exit(1);
@@ -139,12 +268,17 @@ fn main() {
     let movies = download_result.unwrap();
     println!("{}", "download completed successfully".green());
 
+    let save_options = SaveOptions {
+        to_utf8: matches.is_present("utf8"),
+        to_srt: matches.is_present("to-srt"),
+    };
+
     let mut ok = 0;
     for movie in &movies {
         if movie.subs.len() == 0 {
             continue;
         }
-        if let Err(e) = movie.save_subs() {
+        if let Err(e) = movie.save_subs_with(&save_options) {
             eprintln!(
                 "{} {} {} ({:?})",
                 "saving subtitles for".red(),
@@ -166,4 +300,17 @@ fn main() {
             (if ok == 1 { "" } else { "s" }).green()
         );
     }
+
+    if !matches.is_present("no-cache") {
+        let save_path = if matches.value_of("config").is_some() {
+            user.cache_path().map(Path::to_path_buf)
+        } else {
+            cache_path.map(Path::to_path_buf)
+        };
+        if let Some(path) = &save_path {
+            if let Err(e) = user.save_cache(path) {
+                eprintln!("{} ({:?})", "could not save cache".yellow(), e);
+            }
+        }
+    }
 }
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::movie::{Error, Subtitles};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    timestamp: u64,
+    subs: Vec<Subtitles>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedToken {
+    token: String,
+    // The Content-Location the server handed back with this token, so a
+    // cache hit can keep talking to the same backend instead of assuming
+    // it's always the default login location. Empty for tokens cached
+    // before this field existed, or if the server never sent one.
+    #[serde(default)]
+    api: String,
+    timestamp: u64,
+}
+
+// JSON-backed cache of search results, keyed by (moviehash, moviebytesize,
+// sublanguageid), and of login tokens keyed by username, so repeated runs
+// over the same library skip both the `LogIn` round trip and subtitle
+// searches already seen.
+#[derive(Serialize, Deserialize)]
+pub struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    #[serde(default)]
+    tokens: HashMap<String, CachedToken>,
+    #[serde(skip)]
+    ttl: Duration,
+}
+
+impl Cache {
+    // Session tokens expire on OpenSubtitles' side well before a week is
+    // up, independently of how long `ttl` keeps search results around.
+    const TOKEN_TTL: Duration = Duration::from_secs(8 * 60 * 60);
+
+    pub fn new(ttl: Duration) -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            tokens: HashMap::new(),
+            ttl,
+        }
+    }
+
+    pub fn key(moviehash: &str, moviebytesize: u64, sublanguageid: &str) -> String {
+        format!("{}:{}:{}", moviehash, moviebytesize, sublanguageid)
+    }
+
+    // Falls back to an empty cache if `path` is missing or unparseable,
+    // rather than surfacing an error: a cold cache is just a cache miss.
+    pub fn load(path: &Path, ttl: Duration) -> Cache {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Cache>(&contents).ok())
+            .map(|mut cache| {
+                cache.ttl = ttl;
+                cache
+            })
+            .unwrap_or_else(|| Cache::new(ttl))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string(self)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Vec<Subtitles>> {
+        let now = Cache::now();
+        self.entries
+            .get(key)
+            .filter(|entry| now.saturating_sub(entry.timestamp) < self.ttl.as_secs())
+            .map(|entry| &entry.subs)
+    }
+
+    pub fn insert(&mut self, key: String, subs: Vec<Subtitles>) {
+        self.entries.insert(
+            key,
+            CacheEntry {
+                timestamp: Cache::now(),
+                subs,
+            },
+        );
+    }
+
+    // Returns the cached (token, api endpoint) pair; `api` is empty if this
+    // token predates that field being tracked.
+    pub fn get_token(&self, username: &str) -> Option<(&str, &str)> {
+        let now = Cache::now();
+        self.tokens
+            .get(username)
+            .filter(|t| now.saturating_sub(t.timestamp) < Cache::TOKEN_TTL.as_secs())
+            .map(|t| (t.token.as_str(), t.api.as_str()))
+    }
+
+    pub fn set_token(&mut self, username: &str, token: String, api: String) {
+        self.tokens.insert(
+            username.to_owned(),
+            CachedToken {
+                token,
+                api,
+                timestamp: Cache::now(),
+            },
+        );
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,35 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::movie::{Error, SubFilter};
+
+// On-disk, hand-edited counterpart to `Cache`: settings a user sets once and
+// forgets, rather than data the program accumulates itself. TOML, not the
+// `serde_json` used for the cache, since this file is meant to be written
+// and read by a person.
+#[derive(Debug, Deserialize, Default)]
+pub struct Config {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub language: Option<String>,
+    pub cache_path: Option<PathBuf>,
+    pub cache_ttl_secs: Option<u64>,
+    pub retries: Option<u32>,
+    #[serde(default)]
+    pub filter: SubFilter,
+}
+
+impl Config {
+    // Lets the password be kept out of the config file entirely.
+    const PASSWORD_ENV_VAR: &'static str = "SUBPLS_PASSWORD";
+
+    pub fn load(path: &Path) -> Result<Config, Error> {
+        let contents = fs::read_to_string(path)?;
+        let mut config: Config = toml::from_str(&contents)?;
+        if let Ok(password) = std::env::var(Config::PASSWORD_ENV_VAR) {
+            config.password = Some(password);
+        }
+        Ok(config)
+    }
+}
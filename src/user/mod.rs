@@ -1,32 +1,134 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+pub mod cache;
+pub mod config;
 pub mod movie;
+pub mod transport;
+
+pub use cache::Cache;
+pub use config::Config;
 pub use movie::Error;
 pub use movie::Movie;
+pub use movie::SaveOptions;
+pub use movie::SubFilter;
 pub use movie::Subtitles;
+pub use transport::{HttpTransport, Transport};
 
-#[derive(Debug)]
 pub struct User {
     api: String,
     token: String,
-    sublanguageid: String,
+    languages: Vec<String>,
+    username: String,
+    password: String,
+    retries: u32,
+    transport: Box<dyn Transport>,
+    cache: Option<Cache>,
+    cache_path: Option<PathBuf>,
+    cache_ttl: Duration,
+    pub default_filter: SubFilter,
 }
 
 impl User {
+    pub const DEFAULT_RETRIES: u32 = 5;
+    const RETRY_BASE_DELAY_MS: u64 = 500;
+
+    fn default_cache_ttl() -> Duration {
+        Duration::from_secs(7 * 24 * 60 * 60)
+    }
+
+    // `language` is OpenSubtitles' SubLanguageID syntax: a single code or a
+    // comma-joined list, e.g. "eng,spa".
+    fn parse_languages(language: &str) -> Vec<String> {
+        language
+            .split(',')
+            .map(|l| l.trim().to_owned())
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+
+    // Shared by every login path: rejects a `--language` that, once split
+    // and trimmed, names nothing at all (e.g. an empty string or a bare
+    // ",").
+    fn parsed_languages(language: &str) -> Result<Vec<String>, Error> {
+        let languages = User::parse_languages(language);
+        if languages.is_empty() {
+            return Err(Error::NoLanguage);
+        }
+        Ok(languages)
+    }
+
+    fn sublanguageid(&self) -> String {
+        self.languages.join(",")
+    }
+
     // login to OS server, should be called always when starting talking with
     // server. It returns token, which must be used in later communication.
-    pub fn login(
+    pub async fn login(
+        username: &str,
+        password: &str,
+        language: &str,
+    ) -> Result<User, Error> {
+        User::login_with_retries(username, password, language, User::DEFAULT_RETRIES)
+            .await
+    }
+
+    pub async fn login_with_retries(
+        username: &str,
+        password: &str,
+        language: &str,
+        retries: u32,
+    ) -> Result<User, Error> {
+        User::with_transport(
+            username,
+            password,
+            language,
+            retries,
+            Box::new(HttpTransport::default()),
+        )
+        .await
+    }
+
+    pub async fn with_transport(
         username: &str,
         password: &str,
         language: &str,
+        retries: u32,
+        transport: Box<dyn Transport>,
     ) -> Result<User, Error> {
-        let response = User::login_request(username, password, language)?;
+        let languages = User::parsed_languages(language)?;
+
+        let mut attempt = 0;
+        let response = loop {
+            attempt += 1;
+            match User::login_request(transport.as_ref(), username, password, language)
+                .await
+                .and_then(User::checked_response)
+            {
+                Ok(response) => break response,
+                Err(e) if attempt < retries && User::is_retryable(&e) => {
+                    tokio::time::sleep(User::backoff_delay(attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
         let mut user = User {
             api: String::new(),
             token: String::new(),
-            sublanguageid: language.to_owned(),
+            languages,
+            username: username.to_owned(),
+            password: password.to_owned(),
+            retries,
+            transport,
+            cache: None,
+            cache_path: None,
+            cache_ttl: User::default_cache_ttl(),
+            default_filter: SubFilter::default(),
         };
-        User::response_status(&response)?;
         match response.get("token").and_then(|x| x.as_str()) {
             Some(token) => user.token = token.to_string(),
             None => return Err(Error::NoToken),
@@ -43,30 +145,194 @@ impl User {
         Ok(user)
     }
 
-    pub fn search(&self, mut movies: Vec<Movie>) -> Result<Vec<Movie>, Error> {
-        let response = self.search_request(&movies)?;
-        User::response_status(&response)?;
-        let mut subs_map = User::extract_subids(response);
-        for mut movie in &mut movies {
+    // Like `login_with_retries`, but reuses a non-expired token from the
+    // on-disk cache instead of re-authenticating, and remembers the token
+    // it ends up with for next time. `refresh` starts from an empty cache,
+    // discarding both the stored token and any cached search results.
+    pub async fn login_with_cache(
+        username: &str,
+        password: &str,
+        language: &str,
+        retries: u32,
+        cache_path: &Path,
+        refresh: bool,
+    ) -> Result<User, Error> {
+        let languages = User::parsed_languages(language)?;
+
+        let mut cache = if refresh {
+            Cache::new(User::default_cache_ttl())
+        } else {
+            Cache::load(cache_path, User::default_cache_ttl())
+        };
+        if !refresh {
+            if let Some((token, api)) = cache.get_token(username) {
+                let api = if api.is_empty() {
+                    User::LOGIN_LOCATION.to_string()
+                } else {
+                    api.to_string()
+                };
+                let mut user = User {
+                    api,
+                    token: token.to_string(),
+                    languages,
+                    username: username.to_owned(),
+                    password: password.to_owned(),
+                    retries,
+                    transport: Box::new(HttpTransport::default()),
+                    cache: None,
+                    cache_path: None,
+                    cache_ttl: User::default_cache_ttl(),
+                    default_filter: SubFilter::default(),
+                };
+                user.cache = Some(cache);
+                user.cache_path = Some(cache_path.to_path_buf());
+                return Ok(user);
+            }
+        }
+
+        let mut user =
+            User::login_with_retries(username, password, language, retries).await?;
+        cache.set_token(username, user.token.clone(), user.api.clone());
+        user.cache = Some(cache);
+        user.cache_path = Some(cache_path.to_path_buf());
+        Ok(user)
+    }
+
+    // Reads username/password/language/retries/filter/cache settings out of
+    // a `Config` file and logs in with them, so repeated runs over the same
+    // library can be a single `--config` flag instead of a handful of CLI
+    // arguments every time.
+    pub async fn from_config(path: &Path) -> Result<User, Error> {
+        let config = Config::load(path)?;
+        let username = config.username.unwrap_or_default();
+        let password = config.password.unwrap_or_default();
+        let language = config.language.unwrap_or_else(|| "eng".to_string());
+        let retries = config.retries.unwrap_or(User::DEFAULT_RETRIES);
+
+        let mut user =
+            User::login_with_retries(&username, &password, &language, retries).await?;
+        user.default_filter = config.filter;
+        if let Some(ttl) = config.cache_ttl_secs {
+            user.set_cache_ttl(Duration::from_secs(ttl));
+        }
+        if let Some(cache_path) = &config.cache_path {
+            user.load_cache(cache_path);
+        }
+        Ok(user)
+    }
+
+    pub fn load_cache(&mut self, path: &Path) {
+        self.cache = Some(Cache::load(path, self.cache_ttl));
+        self.cache_path = Some(path.to_path_buf());
+    }
+
+    pub fn save_cache(&self, path: &Path) -> Result<(), Error> {
+        match &self.cache {
+            Some(cache) => cache.save(path),
+            None => Ok(()),
+        }
+    }
+
+    // Path `load_cache` (or `login_with_cache`/`from_config`) was given, so
+    // callers don't have to separately track it just to save back to the
+    // same file a session started from.
+    pub fn cache_path(&self) -> Option<&Path> {
+        self.cache_path.as_deref()
+    }
+
+    pub fn set_cache_ttl(&mut self, ttl: Duration) {
+        self.cache_ttl = ttl;
+    }
+
+    pub async fn search(&mut self, mut movies: Vec<Movie>) -> Result<Vec<Movie>, Error> {
+        let mut misses = Vec::new();
+        for (i, movie) in movies.iter_mut().enumerate() {
             if let Some(os_info) = &movie.os_info {
-                movie.subs = subs_map.remove(&os_info.hash).unwrap_or_default();
+                let key =
+                    Cache::key(&os_info.hash, os_info.size, &self.sublanguageid());
+                if let Some(cached) = self.cache.as_ref().and_then(|c| c.get(&key)) {
+                    movie.subs = cached.clone();
+                    continue;
+                }
+            }
+            misses.push(i);
+        }
+
+        let hash_indices: Vec<usize> = misses
+            .iter()
+            .copied()
+            .filter(|&i| movies[i].os_info.is_some())
+            .collect();
+        if !hash_indices.is_empty() {
+            // Owned (hash, size) pairs rather than `&Movie`s: the closure
+            // below is rebuilt and re-run on every retry attempt, and an
+            // `&Movie` borrowed out of `movies` can't coexist with the
+            // `movies[i].subs = ...` writes once the retries are done.
+            let hashes: Vec<(String, u64)> = hash_indices
+                .iter()
+                .map(|&i| {
+                    let os_info = movies[i].os_info.as_ref().unwrap();
+                    (os_info.hash.clone(), os_info.size)
+                })
+                .collect();
+            let response = self
+                .call_with_retry(|user| Box::pin(user.search_request(hashes.clone())))
+                .await?;
+            let mut subs_map = User::extract_subids(response);
+            for i in hash_indices {
+                if let Some(os_info) = &movies[i].os_info {
+                    let key =
+                        Cache::key(&os_info.hash, os_info.size, &self.sublanguageid());
+                    let subs = subs_map.remove(&os_info.hash).unwrap_or_default();
+                    if let Some(cache) = &mut self.cache {
+                        cache.insert(key, subs.clone());
+                    }
+                    movies[i].subs = subs;
+                }
             }
         }
+
+        // Fall back to a title/IMDB-id text search for anything that still
+        // came up empty, whether because it had no moviehash at all or
+        // because the hash lookup itself found nothing.
+        let text_indices: Vec<usize> = movies
+            .iter()
+            .enumerate()
+            .filter(|(_, movie)| movie.subs.is_empty())
+            .map(|(i, _)| i)
+            .collect();
+        if !text_indices.is_empty() {
+            let queries: Vec<(String, Option<String>)> = text_indices
+                .iter()
+                .map(|&i| (movies[i].search_query(), movies[i].imdb_id.clone()))
+                .collect();
+            let response = self
+                .call_with_retry(|user| Box::pin(user.text_search_request(queries.clone())))
+                .await?;
+            let mut by_query = User::extract_text_subids(response);
+            for (pos, &i) in text_indices.iter().enumerate() {
+                if let Some(subs) = by_query.remove(&(pos as i64)) {
+                    movies[i].subs = subs;
+                }
+            }
+        }
+
         Ok(movies)
     }
 
-    pub fn download(
-        &self,
+    pub async fn download(
+        &mut self,
         mut movies: Vec<Movie>,
     ) -> Result<Vec<Movie>, Error> {
         let mut ids = Vec::new();
         for movie in &movies {
             for sub in &movie.subs {
-                ids.push(sub);
+                ids.push(sub.id.clone());
             }
         }
-        let response = self.download_request(ids)?;
-        User::response_status(&response)?;
+        let response = self
+            .call_with_retry(|user| Box::pin(user.download_request(ids.clone())))
+            .await?;
         let mut b64gzs = HashMap::new();
         let results = response.get("data").and_then(|data| data.as_array());
         let mut extract_item = |item: &xmlrpc::Value| {
@@ -113,6 +379,8 @@ impl User {
                 ("SubFormat", ""),
                 ("SubRating", ""),
                 ("SubLanguageID", ""),
+                ("SubHearingImpaired", ""),
+                ("SubDownloadsCnt", ""),
             ];
             for (ref name, ref mut val) in &mut fields {
                 if let Some(v) = item.get(*name).and_then(|x| x.as_str()) {
@@ -121,6 +389,10 @@ impl User {
                     return;
                 }
             }
+            let encoding = item
+                .get("SubEncoding")
+                .and_then(|x| x.as_str())
+                .map(String::from);
             let hash = fields[0].1.to_owned();
             let subs = ret.entry(hash).or_insert(Vec::new());
             subs.push(Subtitles {
@@ -128,7 +400,70 @@ impl User {
                 id: String::from(fields[1].1),
                 rating: fields[3].1.parse().unwrap_or(0f64),
                 lang: String::from(fields[4].1),
+                hearing_impaired: fields[5].1 == "1",
+                downloads: fields[6].1.parse().unwrap_or(0),
+                b64gz: None,
+                from_hash: true,
+                encoding,
+            })
+        };
+        results.map(|array| {
+            for item in array {
+                extract_item(item)
+            }
+        });
+        ret
+    }
+
+    // Same shape as `extract_subids`, but for a text/IMDB-id search: results
+    // aren't tied to a moviehash, so they're keyed by the `QueryNumber`
+    // OpenSubtitles echoes back, i.e. the index of the query that matched
+    // within the `SearchSubtitles` array we sent.
+    fn extract_text_subids(response: xmlrpc::Value) -> HashMap<i64, Vec<Subtitles>> {
+        let mut ret = HashMap::new();
+        let results = response.get("data").and_then(|data| data.as_array());
+        let mut extract_item = |item: &xmlrpc::Value| {
+            if item.as_struct().is_none() {
+                return;
+            }
+            let item = item.as_struct().unwrap();
+            let query_number = match item.get("QueryNumber").and_then(|x| x.as_str()) {
+                Some(v) => match v.parse::<i64>() {
+                    Ok(n) => n,
+                    Err(_) => return,
+                },
+                None => return,
+            };
+            let mut fields = [
+                ("IDSubtitleFile", ""),
+                ("SubFormat", ""),
+                ("SubRating", ""),
+                ("SubLanguageID", ""),
+                ("SubHearingImpaired", ""),
+                ("SubDownloadsCnt", ""),
+            ];
+            for (ref name, ref mut val) in &mut fields {
+                if let Some(v) = item.get(*name).and_then(|x| x.as_str()) {
+                    *val = v;
+                } else {
+                    return;
+                }
+            }
+            let encoding = item
+                .get("SubEncoding")
+                .and_then(|x| x.as_str())
+                .map(String::from);
+            let subs = ret.entry(query_number).or_insert(Vec::new());
+            subs.push(Subtitles {
+                id: String::from(fields[0].1),
+                format: String::from(fields[1].1),
+                rating: fields[2].1.parse().unwrap_or(0f64),
+                lang: String::from(fields[3].1),
+                hearing_impaired: fields[4].1 == "1",
+                downloads: fields[5].1.parse().unwrap_or(0),
                 b64gz: None,
+                from_hash: false,
+                encoding,
             })
         };
         results.map(|array| {
@@ -142,47 +477,77 @@ impl User {
     const LOGIN_LOCATION: &'static str =
         "https://api.opensubtitles.org/xml-rpc";
 
-    fn login_request(
+    async fn login_request(
+        transport: &dyn Transport,
         username: &str,
         password: &str,
         language: &str,
-    ) -> Result<xmlrpc::Value, xmlrpc::Error> {
+    ) -> Result<xmlrpc::Value, Error> {
         let request = xmlrpc::Request::new("LogIn")
             .arg(username)
             .arg(password)
             .arg(language)
             .arg("TemporaryUserAgent");
-        Ok(request.call_url(User::LOGIN_LOCATION)?)
+        transport.call(User::LOGIN_LOCATION, request).await
     }
 
-    fn search_request(
+    async fn search_request(
         &self,
-        movies: &Vec<Movie>,
+        hashes: Vec<(String, u64)>,
     ) -> Result<xmlrpc::Value, Error> {
         let mut prepared = Vec::new();
-        for movie in movies {
-            if let Some(os_info) = &movie.os_info {
-                let entry = xmlrpc::Value::Struct(
-                    vec![
-                        (
-                            "moviehash".to_string(),
-                            xmlrpc::Value::from(os_info.hash.as_str()),
-                        ),
-                        (
-                            "moviebytesize".to_string(),
-                            xmlrpc::Value::from(os_info.size as i64),
-                        ),
-                        (
-                            "sublanguageid".to_string(),
-                            xmlrpc::Value::from(self.sublanguageid.as_str()),
-                        ),
-                    ]
-                    .into_iter()
-                    .collect(),
-                );
+        for (hash, size) in hashes {
+            let entry = xmlrpc::Value::Struct(
+                vec![
+                    ("moviehash".to_string(), xmlrpc::Value::from(hash.as_str())),
+                    (
+                        "moviebytesize".to_string(),
+                        xmlrpc::Value::from(size as i64),
+                    ),
+                    (
+                        "sublanguageid".to_string(),
+                        xmlrpc::Value::from(self.sublanguageid().as_str()),
+                    ),
+                ]
+                .into_iter()
+                .collect(),
+            );
+
+            prepared.push(entry);
+        }
+        if prepared.len() < 1 {
+            return Err(Error::NothingToSearch);
+        }
+        let request = xmlrpc::Request::new("SearchSubtitles")
+            .arg(self.token.as_str())
+            .arg(xmlrpc::Value::Array(prepared));
+        self.transport.call(self.api.as_str(), request).await
+    }
 
-                prepared.push(entry);
+    // Text/IMDB-id fallback for movies whose moviehash came up empty. Each
+    // entry lands in the request array at the same position it has in
+    // `queries`, which is what lets `extract_text_subids` match responses
+    // back up via `QueryNumber`.
+    async fn text_search_request(
+        &self,
+        queries: Vec<(String, Option<String>)>,
+    ) -> Result<xmlrpc::Value, Error> {
+        let mut prepared = Vec::new();
+        for (query, imdb_id) in queries {
+            let mut fields = vec![
+                ("query".to_string(), xmlrpc::Value::from(query.as_str())),
+                (
+                    "sublanguageid".to_string(),
+                    xmlrpc::Value::from(self.sublanguageid().as_str()),
+                ),
+            ];
+            if let Some(imdb_id) = &imdb_id {
+                fields.push((
+                    "imdbid".to_string(),
+                    xmlrpc::Value::from(imdb_id.as_str()),
+                ));
             }
+            prepared.push(xmlrpc::Value::Struct(fields.into_iter().collect()));
         }
         if prepared.len() < 1 {
             return Err(Error::NothingToSearch);
@@ -190,22 +555,22 @@ impl User {
         let request = xmlrpc::Request::new("SearchSubtitles")
             .arg(self.token.as_str())
             .arg(xmlrpc::Value::Array(prepared));
-        Ok(request.call_url(self.api.as_str())?)
+        self.transport.call(self.api.as_str(), request).await
     }
 
-    fn download_request(
+    async fn download_request(
         &self,
-        sub_ids: Vec<&Subtitles>,
-    ) -> Result<xmlrpc::Value, xmlrpc::Error> {
+        sub_ids: Vec<String>,
+    ) -> Result<xmlrpc::Value, Error> {
         let request = xmlrpc::Request::new("DownloadSubtitles")
             .arg(self.token.as_str())
             .arg(xmlrpc::Value::Array(
                 sub_ids
                     .into_iter()
-                    .map(|x| xmlrpc::Value::from(x.id.as_str()))
+                    .map(|x| xmlrpc::Value::from(x.as_str()))
                     .collect(),
             ));
-        Ok(request.call_url(&self.api)?)
+        self.transport.call(self.api.as_str(), request).await
     }
 
     fn response_status(response: &xmlrpc::Value) -> Result<(), Error> {
@@ -218,4 +583,279 @@ impl User {
         }
         Err(Error::Malformed)
     }
+
+    fn checked_response(response: xmlrpc::Value) -> Result<xmlrpc::Value, Error> {
+        User::response_status(&response)?;
+        Ok(response)
+    }
+
+    // Runs `call` (rebuilt from scratch on every attempt, since a relogin
+    // changes the token it needs to embed) up to `self.retries` times,
+    // retrying on transient transport errors and rate-limiting, and
+    // transparently re-authenticating once the session token has expired.
+    async fn call_with_retry<F>(&mut self, mut call: F) -> Result<xmlrpc::Value, Error>
+    where
+        F: for<'a> FnMut(
+            &'a User,
+        )
+            -> Pin<Box<dyn Future<Output = Result<xmlrpc::Value, Error>> + 'a>>,
+    {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match call(self).await.and_then(User::checked_response) {
+                Ok(response) => return Ok(response),
+                Err(e) if attempt < self.retries && User::is_expired_token(&e) => {
+                    self.relogin().await?;
+                }
+                Err(e) if attempt < self.retries && User::is_retryable(&e) => {
+                    tokio::time::sleep(User::backoff_delay(attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn relogin(&mut self) -> Result<(), Error> {
+        let response = User::login_request(
+            self.transport.as_ref(),
+            &self.username,
+            &self.password,
+            &self.sublanguageid(),
+        )
+        .await
+        .and_then(User::checked_response)?;
+        self.token = response
+            .get("token")
+            .and_then(|x| x.as_str())
+            .map(str::to_owned)
+            .ok_or(Error::NoToken)?;
+        if let Some(cache) = &mut self.cache {
+            cache.set_token(&self.username, self.token.clone(), self.api.clone());
+        }
+        Ok(())
+    }
+
+    fn is_retryable(error: &Error) -> bool {
+        match error {
+            Error::Xmlrpc(_) | Error::Task => true,
+            Error::BadStatus(status) => {
+                status.starts_with("429")
+                    || status.starts_with("503")
+                    || status.starts_with("520")
+            }
+            _ => false,
+        }
+    }
+
+    fn is_expired_token(error: &Error) -> bool {
+        match error {
+            Error::BadStatus(status) => {
+                status.starts_with("401") || status.starts_with("406")
+            }
+            _ => false,
+        }
+    }
+
+    const RETRY_MAX_DELAY_MS: u64 = 16_000;
+
+    // Exponential backoff (base 500ms, doubling each attempt) capped at
+    // ~16s, plus up to 25% jitter so retrying clients don't all wake up in
+    // lockstep.
+    fn backoff_delay(attempt: u32) -> Duration {
+        let base = User::RETRY_BASE_DELAY_MS
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+            .min(User::RETRY_MAX_DELAY_MS);
+        Duration::from_millis(base + User::jitter_ms(base))
+    }
+
+    // Cheap pseudo-random jitter derived from the low bits of the current
+    // time; avoids pulling in a `rand` dependency just for this.
+    fn jitter_ms(base_ms: u64) -> u64 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0) as u64;
+        nanos % (base_ms / 4 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    // Hands back one queued response per call, so call_with_retry's
+    // retry/relogin branching can be exercised without a live
+    // OpenSubtitles endpoint.
+    struct MockTransport {
+        responses: Mutex<VecDeque<Result<xmlrpc::Value, Error>>>,
+    }
+
+    impl MockTransport {
+        fn new(responses: Vec<Result<xmlrpc::Value, Error>>) -> MockTransport {
+            MockTransport {
+                responses: Mutex::new(responses.into_iter().collect()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Transport for MockTransport {
+        async fn call(
+            &self,
+            _url: &str,
+            _request: xmlrpc::Request,
+        ) -> Result<xmlrpc::Value, Error> {
+            self.responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("MockTransport ran out of queued responses")
+        }
+    }
+
+    fn status_response(status: &str) -> xmlrpc::Value {
+        xmlrpc::Value::Struct(
+            vec![("status".to_string(), xmlrpc::Value::from(status))]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn login_response(token: &str) -> xmlrpc::Value {
+        xmlrpc::Value::Struct(
+            vec![
+                ("status".to_string(), xmlrpc::Value::from("200 OK")),
+                ("token".to_string(), xmlrpc::Value::from(token)),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    fn test_user(transport: MockTransport) -> User {
+        User {
+            api: "https://example.test/xml-rpc".to_string(),
+            token: "initial-token".to_string(),
+            languages: vec!["eng".to_string()],
+            username: "user".to_string(),
+            password: "pass".to_string(),
+            retries: 3,
+            transport: Box::new(transport),
+            cache: None,
+            cache_path: None,
+            cache_ttl: User::default_cache_ttl(),
+            default_filter: SubFilter::default(),
+        }
+    }
+
+    fn hash_sub_struct(hash: &str, id: &str) -> xmlrpc::Value {
+        xmlrpc::Value::Struct(
+            vec![
+                ("MovieHash".to_string(), xmlrpc::Value::from(hash)),
+                ("IDSubtitleFile".to_string(), xmlrpc::Value::from(id)),
+                ("SubFormat".to_string(), xmlrpc::Value::from("srt")),
+                ("SubRating".to_string(), xmlrpc::Value::from("8.0")),
+                ("SubLanguageID".to_string(), xmlrpc::Value::from("eng")),
+                ("SubHearingImpaired".to_string(), xmlrpc::Value::from("0")),
+                ("SubDownloadsCnt".to_string(), xmlrpc::Value::from("100")),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    fn text_sub_struct(query_number: &str, id: &str) -> xmlrpc::Value {
+        xmlrpc::Value::Struct(
+            vec![
+                ("QueryNumber".to_string(), xmlrpc::Value::from(query_number)),
+                ("IDSubtitleFile".to_string(), xmlrpc::Value::from(id)),
+                ("SubFormat".to_string(), xmlrpc::Value::from("srt")),
+                ("SubRating".to_string(), xmlrpc::Value::from("7.0")),
+                ("SubLanguageID".to_string(), xmlrpc::Value::from("spa")),
+                ("SubHearingImpaired".to_string(), xmlrpc::Value::from("0")),
+                ("SubDownloadsCnt".to_string(), xmlrpc::Value::from("10")),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn extract_subids_groups_by_moviehash() {
+        let response = xmlrpc::Value::Struct(
+            vec![(
+                "data".to_string(),
+                xmlrpc::Value::Array(vec![
+                    hash_sub_struct("aaa", "1"),
+                    hash_sub_struct("aaa", "2"),
+                    hash_sub_struct("bbb", "3"),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let grouped = User::extract_subids(response);
+        assert_eq!(grouped.get("aaa").map(Vec::len), Some(2));
+        assert_eq!(grouped.get("bbb").map(Vec::len), Some(1));
+    }
+
+    #[test]
+    fn extract_text_subids_groups_by_query_number() {
+        let response = xmlrpc::Value::Struct(
+            vec![(
+                "data".to_string(),
+                xmlrpc::Value::Array(vec![
+                    text_sub_struct("0", "1"),
+                    text_sub_struct("1", "2"),
+                    text_sub_struct("1", "3"),
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+        );
+
+        let grouped = User::extract_text_subids(response);
+        assert_eq!(grouped.get(&0).map(Vec::len), Some(1));
+        assert_eq!(grouped.get(&1).map(Vec::len), Some(2));
+    }
+
+    #[tokio::test]
+    async fn call_with_retry_retries_transient_errors() {
+        let transport = MockTransport::new(vec![
+            Err(Error::BadStatus("503 Service Unavailable".to_string())),
+            Ok(status_response("200 OK")),
+        ]);
+        let mut user = test_user(transport);
+
+        let result = user
+            .call_with_retry(|user| {
+                Box::pin(User::login_request(user.transport.as_ref(), "u", "p", "eng"))
+            })
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn call_with_retry_relogs_in_on_expired_token() {
+        let transport = MockTransport::new(vec![
+            Err(Error::BadStatus("401 Unauthorized".to_string())),
+            Ok(login_response("fresh-token")),
+            Ok(status_response("200 OK")),
+        ]);
+        let mut user = test_user(transport);
+
+        let result = user
+            .call_with_retry(|user| {
+                Box::pin(User::login_request(user.transport.as_ref(), "u", "p", "eng"))
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(user.token, "fresh-token");
+    }
 }
@@ -0,0 +1,24 @@
+use encoding_rs::{Encoding, WINDOWS_1250};
+
+// Transcodes subtitle bytes to UTF-8. Prefers `hint` (OpenSubtitles' own
+// `SubEncoding` field) when it names a codec `encoding_rs` recognizes;
+// otherwise assumes the bytes are already UTF-8 and, if they aren't, falls
+// back to Windows-1250, which covers the bulk of the legacy single-byte
+// subtitle dumps OpenSubtitles serves. Not a full charset sniffer, but
+// enough to turn the common mojibake case into readable text.
+pub fn to_utf8(bytes: &[u8], hint: Option<&str>) -> String {
+    if let Some(label) = hint {
+        if let Some(encoding) = Encoding::for_label(label.as_bytes()) {
+            let (decoded, _, _) = encoding.decode(bytes);
+            return decoded.into_owned();
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        Err(_) => {
+            let (decoded, _, _) = WINDOWS_1250.decode(bytes);
+            decoded.into_owned()
+        }
+    }
+}
@@ -8,7 +8,13 @@ pub enum Error {
     Malformed,
     NothingToSearch,
     NothingToSave,
+    NoLanguage,
     BadPath,
+    Cache(serde_json::Error),
+    Config(toml::de::Error),
+    // A `tokio::task::spawn_blocking` task (e.g. the blocking XML-RPC
+    // round trip in `HttpTransport`) panicked or was cancelled.
+    Task,
 }
 
 impl From<std::io::Error> for Error {
@@ -28,3 +34,15 @@ impl From<base64::DecodeError> for Error {
         Error::Base64
     }
 }
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Cache(error)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Config(error)
+    }
+}
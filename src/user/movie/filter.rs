@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+use super::Subtitles;
+
+// A declarative subtitle selection policy, applied per language so a search
+// across several SubLanguageIDs still keeps the best candidate(s) for each.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubFilter {
+    pub format_priority: Vec<String>,
+    pub min_rating: f64,
+    pub prefer_hearing_impaired: bool,
+    pub max_results: usize,
+}
+
+impl Default for SubFilter {
+    fn default() -> SubFilter {
+        SubFilter {
+            format_priority: Vec::new(),
+            min_rating: 0f64,
+            prefer_hearing_impaired: false,
+            max_results: 1,
+        }
+    }
+}
+
+impl SubFilter {
+    // Higher is better: rating dominates, download count breaks ties, and
+    // matching the hearing-impaired preference or a preferred format nudges
+    // otherwise-equal candidates ahead of one another.
+    pub fn score(&self, sub: &Subtitles) -> f64 {
+        // A hash match always outranks a text match: it's tied to this
+        // exact file, while a title/IMDB-id query is only ever a guess.
+        let mut score = if sub.from_hash { 1_000_000_000_000f64 } else { 0f64 };
+        // A 0.1 rating step (1e7) outweighs the largest download-count
+        // contribution (1e6), so downloads only ever break rating ties.
+        score += sub.rating * 100_000_000f64;
+        score += (sub.downloads as f64).min(1_000_000f64);
+        if sub.hearing_impaired == self.prefer_hearing_impaired {
+            score += 0.5;
+        }
+        if let Some(rank) = self
+            .format_priority
+            .iter()
+            .position(|f| f.eq_ignore_ascii_case(&sub.format))
+        {
+            score += (self.format_priority.len() - rank) as f64 * 0.1;
+        }
+        score
+    }
+}
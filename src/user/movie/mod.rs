@@ -1,22 +1,40 @@
 use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path::PathBuf;
 
+pub mod encoding;
 pub mod error;
+pub mod filter;
 pub mod hash;
+pub mod save;
 
 pub use error::*;
+pub use filter::SubFilter;
 use hash::*;
+pub use save::SaveOptions;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Subtitles {
     pub id: String,
     pub lang: String,
     pub format: String,
     pub rating: f64, // <1,10> + 0
+    pub hearing_impaired: bool,
+    pub downloads: u64,
     pub b64gz: Option<String>,
+    // Hash matches are found from an exact moviehash/moviebytesize lookup;
+    // text matches come from a fuzzier title/IMDB-id query and are ranked
+    // below them by `SubFilter::score`.
+    pub from_hash: bool,
+    // OpenSubtitles' own guess at the payload's charset (its `SubEncoding`
+    // field), used by `save_subs_with` as a hint before falling back to a
+    // UTF-8/Windows-1250 sniff.
+    #[serde(default)]
+    pub encoding: Option<String>,
 }
 
 #[derive(Debug)]
@@ -24,6 +42,11 @@ pub struct Movie {
     pub path: PathBuf,
     pub subs: Vec<Subtitles>,
     pub os_info: Option<Hash>,
+    // Overrides for the fallback title/IMDB-id search used when the
+    // moviehash lookup comes back empty; `query` defaults to a title
+    // guessed from the filename when unset.
+    pub query: Option<String>,
+    pub imdb_id: Option<String>,
 }
 
 impl Movie {
@@ -32,6 +55,8 @@ impl Movie {
             path: path,
             os_info: None,
             subs: Vec::new(),
+            query: None,
+            imdb_id: None,
         }
     }
 
@@ -48,35 +73,94 @@ impl Movie {
         Ok(())
     }
 
+    // Best-effort title guess from the filename: drop the extension and
+    // swap the usual release-name separators for spaces. Good enough to
+    // seed a text search; `query` can always override it.
+    pub fn guessed_query(&self) -> String {
+        let stem = self
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        stem.chars()
+            .map(|c| if c == '.' || c == '_' { ' ' } else { c })
+            .collect()
+    }
+
+    pub fn search_query(&self) -> String {
+        self.query.clone().unwrap_or_else(|| self.guessed_query())
+    }
+
+    // Keeps the best subtitle within each language present in `self.subs`
+    // under the default `SubFilter` (hash matches first, then highest
+    // rating), so a search across several SubLanguageIDs retains one
+    // result per language instead of collapsing to a single global winner.
     pub fn filter_subs(&mut self) {
-        let mut highest = -1f64;
-        let mut id = String::new();
-        for sub in &self.subs {
-            if sub.rating > highest {
-                highest = sub.rating;
-                id = sub.id.clone();
+        self.filter_subs_with(&SubFilter::default());
+    }
+
+    // Same grouping as `filter_subs`, but under `filter` instead of the
+    // default policy: drops candidates below `filter.min_rating`, ranks by
+    // `filter.score`, and keeps up to `filter.max_results` per language.
+    pub fn filter_subs_with(&mut self, filter: &SubFilter) {
+        let mut by_lang: HashMap<String, Vec<Subtitles>> = HashMap::new();
+        for sub in self.subs.drain(..) {
+            if sub.rating < filter.min_rating {
+                continue;
             }
+            by_lang.entry(sub.lang.clone()).or_insert_with(Vec::new).push(sub);
+        }
+
+        let mut kept = Vec::new();
+        for (_, mut candidates) in by_lang {
+            candidates.sort_by(|a, b| {
+                filter
+                    .score(b)
+                    .partial_cmp(&filter.score(a))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            candidates.truncate(filter.max_results.max(1));
+            kept.extend(candidates);
         }
-        self.subs.retain(|sub| sub.id == id);
+        self.subs = kept;
     }
 
     pub fn save_subs(&self) -> Result<(), Error> {
+        self.save_subs_with(&SaveOptions::default())
+    }
+
+    // Same as `save_subs`, but honors `options.to_utf8` (transcode the
+    // payload to UTF-8 before writing, using `sub.encoding` as a hint) and
+    // `options.to_srt` (save under a `.srt` extension regardless of the
+    // source format; the contents themselves aren't reformatted yet).
+    pub fn save_subs_with(&self, options: &SaveOptions) -> Result<(), Error> {
+        let mut saved_any = false;
         for sub in &self.subs {
             if sub.b64gz.is_none() {
                 continue;
             }
             let decoded = base64::decode(&sub.b64gz.as_ref().unwrap())?;
-            let extension = format!("{}.{}", sub.lang, sub.format);
+            let format = if options.to_srt { "srt" } else { sub.format.as_str() };
+            let extension = format!("{}.{}", sub.lang, format);
             let mut sub_path = self.path.clone();
             if sub_path.set_extension(extension) == false {
                 return Err(Error::BadPath);
             }
             let mut file = File::create(sub_path.as_path())?;
             let extracted = Movie::decode_reader(decoded)?;
-            file.write_all(extracted.as_slice())?;
-            return Ok(());
+            if options.to_utf8 {
+                let text = encoding::to_utf8(&extracted, sub.encoding.as_deref());
+                file.write_all(text.as_bytes())?;
+            } else {
+                file.write_all(extracted.as_slice())?;
+            }
+            saved_any = true;
+        }
+        if saved_any {
+            Ok(())
+        } else {
+            Err(Error::NothingToSave)
         }
-        Err(Error::NothingToSave)
     }
 
     pub fn present_rating(&self) -> Option<f64> {
@@ -101,3 +185,61 @@ impl Movie {
         Ok(s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(lang: &str, rating: f64, downloads: u64, from_hash: bool) -> Subtitles {
+        Subtitles {
+            id: format!("{}-{}-{}", lang, rating, from_hash),
+            lang: lang.to_string(),
+            format: "srt".to_string(),
+            rating,
+            hearing_impaired: false,
+            downloads,
+            b64gz: None,
+            from_hash,
+            encoding: None,
+        }
+    }
+
+    #[test]
+    fn filter_subs_with_groups_per_language_and_ranks_by_score() {
+        let mut movie = Movie::new(PathBuf::from("movie.mkv"));
+        movie.subs = vec![
+            sub("eng", 9.0, 10, false),
+            sub("eng", 5.0, 1_000, true),
+            sub("spa", 7.0, 0, false),
+        ];
+
+        movie.filter_subs_with(&SubFilter::default());
+
+        assert_eq!(movie.subs.len(), 2);
+        let eng = movie.subs.iter().find(|s| s.lang == "eng").unwrap();
+        // The hash match wins even with a lower rating than the text match,
+        // since SubFilter::score always ranks from_hash above a text match.
+        assert!(eng.from_hash);
+        assert!(movie.subs.iter().any(|s| s.lang == "spa"));
+    }
+
+    #[test]
+    fn filter_subs_with_drops_below_min_rating_and_truncates_to_max_results() {
+        let mut movie = Movie::new(PathBuf::from("movie.mkv"));
+        movie.subs = vec![
+            sub("eng", 9.0, 0, false),
+            sub("eng", 8.0, 0, false),
+            sub("eng", 2.0, 0, false),
+        ];
+
+        let filter = SubFilter {
+            min_rating: 5.0,
+            max_results: 1,
+            ..SubFilter::default()
+        };
+        movie.filter_subs_with(&filter);
+
+        assert_eq!(movie.subs.len(), 1);
+        assert_eq!(movie.subs[0].rating, 9.0);
+    }
+}
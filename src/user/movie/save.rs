@@ -0,0 +1,16 @@
+// Declarative counterpart to `SubFilter`, but for how a subtitle is
+// written to disk rather than which one is picked.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    pub to_utf8: bool,
+    pub to_srt: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> SaveOptions {
+        SaveOptions {
+            to_utf8: false,
+            to_srt: false,
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+
+use super::movie::Error;
+
+// Abstracts the actual XML-RPC round trip so `User` can be exercised with a
+// canned responder instead of the live OpenSubtitles endpoint.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn call(
+        &self,
+        url: &str,
+        request: xmlrpc::Request,
+    ) -> Result<xmlrpc::Value, Error>;
+}
+
+// `xmlrpc` 0.15 keeps its response parser private, so the only public way to
+// get a `Value` back out is `Request::call_url`, which does the whole round
+// trip (send + parse) itself with its own blocking, `reqwest`-backed HTTP
+// client. This crate's `default-tls` / `rustls-tls-native-roots` /
+// `rustls-tls-webpki-roots` features forward to the identically-named
+// features on `xmlrpc`'s own `http` feature, so TLS backend selection stays
+// a Cargo-time choice even though we never touch a `reqwest::Client`
+// ourselves. `call_url` blocks, so it's run via `spawn_blocking` to stay off
+// the async runtime.
+#[derive(Debug, Default, Clone)]
+pub struct HttpTransport;
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn call(
+        &self,
+        url: &str,
+        request: xmlrpc::Request,
+    ) -> Result<xmlrpc::Value, Error> {
+        let url = url.to_string();
+        tokio::task::spawn_blocking(move || request.call_url(url.as_str()))
+            .await
+            .map_err(|_| Error::Task)?
+            .map_err(Error::from)
+    }
+}